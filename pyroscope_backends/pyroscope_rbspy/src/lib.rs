@@ -1,13 +1,22 @@
+mod ring_buffer;
+
+pub use ring_buffer::OverflowPolicy;
+
 use pyroscope::{
     backend::{Backend, Report, StackFrame, StackTrace, State},
     error::{PyroscopeError, Result},
 };
 use rbspy::sampler::Sampler;
+use ring_buffer::StackRing;
 use std::sync::{
     mpsc::{channel, sync_channel, Receiver, Sender, SyncSender},
     Arc, Mutex,
 };
 
+/// Default capacity of the ring buffer between the RubySpy sampler and
+/// `Rbspy::report`, rounded up to a power of two by `StackRing`.
+const DEFAULT_QUEUE_CAPACITY: usize = 8192;
+
 /// Rbspy Configuration
 #[derive(Debug)]
 pub struct RbspyConfig {
@@ -21,6 +30,11 @@ pub struct RbspyConfig {
     time_limit: Option<core::time::Duration>,
     /// Include subprocesses
     with_subprocesses: bool,
+    /// Capacity of the ring buffer holding `StackTrace`s between the
+    /// sampler and `report()`.
+    queue_capacity: usize,
+    /// What to do with incoming samples once the ring buffer is full.
+    overflow_policy: OverflowPolicy,
 }
 
 impl Default for RbspyConfig {
@@ -31,6 +45,8 @@ impl Default for RbspyConfig {
             lock_process: false,
             time_limit: None,
             with_subprocesses: false,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            overflow_policy: OverflowPolicy::Block,
         }
     }
 }
@@ -68,6 +84,23 @@ impl RbspyConfig {
             ..self
         }
     }
+
+    /// Set the ring buffer capacity between the sampler and `report()`.
+    /// Rounded up to a power of two.
+    pub fn queue_capacity(self, queue_capacity: usize) -> Self {
+        RbspyConfig {
+            queue_capacity,
+            ..self
+        }
+    }
+
+    /// Set what happens to incoming samples once the ring buffer is full.
+    pub fn overflow_policy(self, overflow_policy: OverflowPolicy) -> Self {
+        RbspyConfig {
+            overflow_policy,
+            ..self
+        }
+    }
 }
 
 /// Rbspy Backend
@@ -79,8 +112,8 @@ pub struct Rbspy {
     config: RbspyConfig,
     /// Rbspy Sampler
     sampler: Option<Sampler>,
-    /// StackTrace Receiver
-    stack_receiver: Option<Receiver<rbspy::StackTrace>>,
+    /// Ring buffer of `StackTrace`s fed by the sampler's pump thread
+    stack_ring: Option<Arc<StackRing<rbspy::StackTrace>>>,
     /// Error Receiver
     error_receiver: Option<Receiver<std::result::Result<(), anyhow::Error>>>,
     /// Profiling buffer
@@ -97,7 +130,7 @@ impl Rbspy {
     pub fn new(config: RbspyConfig) -> Self {
         Rbspy {
             sampler: None,
-            stack_receiver: None,
+            stack_ring: None,
             error_receiver: None,
             state: State::Uninitialized,
             config,
@@ -158,19 +191,29 @@ impl Backend for Rbspy {
         // Channel for Errors generated by the RubySpy Sampler
         let (error_sender, error_receiver): (ErrorSender, ErrorReceiver) = channel();
 
-        // This is provides enough space for 100 threads.
-        // It might be a better idea to figure out how many threads are running and determine the
-        // size of the channel based on that.
-        let queue_size: usize = self.config.sample_rate as usize * 10 * 100;
-
-        // Channel for StackTraces generated by the RubySpy Sampler
+        // Ring buffer that StackTraces are pumped into, with the configured
+        // capacity and overflow policy. The transfer channel below only
+        // needs to hold a single in-flight sample: once the ring is full,
+        // a `Block` policy stalls the pump thread, which stalls this
+        // channel, which in turn back-pressures the RubySpy Sampler.
+        let stack_ring = Arc::new(StackRing::new(
+            self.config.queue_capacity,
+            self.config.overflow_policy,
+        ));
         let (stack_sender, stack_receiver): (
             SyncSender<rbspy::StackTrace>,
             Receiver<rbspy::StackTrace>,
-        ) = sync_channel(queue_size);
+        ) = sync_channel(1);
+
+        let pump_ring = stack_ring.clone();
+        std::thread::spawn(move || {
+            while let Ok(trace) = stack_receiver.recv() {
+                pump_ring.push(trace);
+            }
+        });
 
         // Set Error and Stack Receivers
-        self.stack_receiver = Some(stack_receiver);
+        self.stack_ring = Some(stack_ring);
         self.error_receiver = Some(error_receiver);
 
         // Get the Sampler
@@ -232,20 +275,30 @@ impl Backend for Rbspy {
             }
         }
 
-        // Collect the StackTrace from the receiver
-        let stack_trace = self
-            .stack_receiver
+        // Drain the StackTrace ring buffer
+        let stack_ring = self
+            .stack_ring
             .as_ref()
-            .ok_or_else(|| PyroscopeError::new("Rbspy: StackTrace receiver is not set"))?
-            .try_iter();
+            .ok_or_else(|| PyroscopeError::new("Rbspy: StackTrace ring is not set"))?;
 
         // Iterate over the StackTrace
-        for trace in stack_trace {
+        for trace in stack_ring.drain() {
             // convert StackTrace
             let own_trace: StackTrace = Into::<StackTraceWrapper>::into(trace).into();
             buffer.lock()?.record(own_trace)?;
         }
 
+        // Surface samples the overflow policy had to discard so users can
+        // detect under-sampling instead of it failing silently.
+        let dropped = stack_ring.take_dropped();
+        if dropped > 0 {
+            log::warn!(
+                "Rbspy: dropped {} stack samples due to {:?} overflow policy",
+                dropped,
+                self.config.overflow_policy
+            );
+        }
+
         let v8: Vec<u8> = buffer.lock()?.to_string().into_bytes();
 
         buffer.lock()?.clear();
@@ -0,0 +1,183 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+
+/// Overflow behavior applied once a [`StackRing`] between the RubySpy
+/// sampler and `Rbspy::report` is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Block the producer until `report()` drains the ring. Matches the
+    /// previous behavior of a plain bounded `sync_channel`.
+    Block,
+    /// Overwrite the oldest buffered `StackTrace` with the new one.
+    DropOldest,
+    /// Discard the incoming `StackTrace`, keeping what's already buffered.
+    DropNewest,
+}
+
+struct RingState<T> {
+    /// Power-of-two-sized slot array; `head`/`tail` are indices into it
+    /// modulo `capacity` via `& mask`, so wraparound is a bitwise AND
+    /// instead of a division.
+    slots: Vec<Option<T>>,
+    head: usize,
+    tail: usize,
+    len: usize,
+}
+
+/// A bounded, power-of-two-sized, head/tail-indexed ring buffer shared
+/// between the sampler's pump thread and `report()`. Applies `policy` once
+/// full and keeps a running count of samples that policy has discarded, so
+/// the caller can see under-sampling instead of the process silently
+/// stalling.
+pub struct StackRing<T> {
+    capacity: usize,
+    mask: usize,
+    policy: OverflowPolicy,
+    state: Mutex<RingState<T>>,
+    not_full: Condvar,
+    dropped: AtomicU64,
+}
+
+impl<T> StackRing<T> {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        let capacity = capacity.next_power_of_two().max(1);
+        let slots = (0..capacity).map(|_| None).collect();
+        StackRing {
+            capacity,
+            mask: capacity - 1,
+            policy,
+            state: Mutex::new(RingState { slots, head: 0, tail: 0, len: 0 }),
+            not_full: Condvar::new(),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Push an item, applying the configured overflow policy once
+    /// `capacity` is reached. Only `Block` waits; `DropOldest`/`DropNewest`
+    /// always return immediately.
+    pub fn push(&self, item: T) {
+        let mut state = self.state.lock().unwrap();
+
+        if self.policy == OverflowPolicy::Block {
+            while state.len == self.capacity {
+                state = self.not_full.wait(state).unwrap();
+            }
+        } else if state.len == self.capacity {
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    state.slots[state.head] = None;
+                    state.head = (state.head + 1) & self.mask;
+                    state.len -= 1;
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                OverflowPolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                OverflowPolicy::Block => unreachable!("handled above"),
+            }
+        }
+
+        let tail = state.tail;
+        state.slots[tail] = Some(item);
+        state.tail = (tail + 1) & self.mask;
+        state.len += 1;
+    }
+
+    /// Drain everything currently buffered, without blocking.
+    pub fn drain(&self) -> Vec<T> {
+        let mut state = self.state.lock().unwrap();
+        let mut drained = Vec::with_capacity(state.len);
+        while state.len > 0 {
+            let head = state.head;
+            if let Some(item) = state.slots[head].take() {
+                drained.push(item);
+            }
+            state.head = (head + 1) & self.mask;
+            state.len -= 1;
+        }
+        drop(state);
+        self.not_full.notify_all();
+        drained
+    }
+
+    /// Number of samples dropped by the overflow policy since the last
+    /// call, resetting the counter back to zero.
+    pub fn take_dropped(&self) -> u64 {
+        self.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn capacity_rounds_up_to_a_power_of_two() {
+        let ring: StackRing<i32> = StackRing::new(3, OverflowPolicy::DropNewest);
+        assert_eq!(ring.capacity, 4);
+    }
+
+    #[test]
+    fn drop_oldest_overwrites_the_stalest_entry() {
+        let ring = StackRing::new(2, OverflowPolicy::DropOldest);
+        ring.push(1);
+        ring.push(2);
+        ring.push(3); // overwrites 1, the oldest slot
+
+        assert_eq!(ring.drain(), vec![2, 3]);
+        assert_eq!(ring.take_dropped(), 1);
+    }
+
+    #[test]
+    fn drop_newest_discards_the_incoming_entry() {
+        let ring = StackRing::new(2, OverflowPolicy::DropNewest);
+        ring.push(1);
+        ring.push(2);
+        ring.push(3); // discarded, capacity already full
+
+        assert_eq!(ring.drain(), vec![1, 2]);
+        assert_eq!(ring.take_dropped(), 1);
+    }
+
+    #[test]
+    fn take_dropped_resets_the_counter() {
+        let ring = StackRing::new(1, OverflowPolicy::DropNewest);
+        ring.push(1);
+        ring.push(2);
+
+        assert_eq!(ring.take_dropped(), 1);
+        assert_eq!(ring.take_dropped(), 0);
+    }
+
+    #[test]
+    fn indexed_slots_survive_wraparound() {
+        let ring = StackRing::new(2, OverflowPolicy::DropOldest);
+        // Push/drain repeatedly so head and tail wrap past the end of the
+        // slot array several times over.
+        for round in 0..5 {
+            ring.push(round * 10);
+            ring.push(round * 10 + 1);
+            assert_eq!(ring.drain(), vec![round * 10, round * 10 + 1]);
+        }
+    }
+
+    #[test]
+    fn block_waits_for_room_then_succeeds() {
+        let ring = Arc::new(StackRing::new(1, OverflowPolicy::Block));
+        ring.push(1);
+
+        let blocked = ring.clone();
+        let handle = std::thread::spawn(move || {
+            blocked.push(2); // must block until the main thread drains
+        });
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(ring.drain(), vec![1]);
+
+        handle.join().unwrap();
+        assert_eq!(ring.drain(), vec![2]);
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2021 Developers of Pyroscope.
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0>. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::error::Result;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use std::collections::HashMap;
+
+/// Auth material for a Pyroscope-compatible backend: a bearer token, HTTP
+/// basic credentials, or an arbitrary set of static headers (e.g. an
+/// `X-Scope-OrgID` tenant header for a multi-tenant backend).
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    Bearer(String),
+    Basic { username: String, password: String },
+    Headers(HashMap<String, String>),
+}
+
+/// Supplies the headers an [`super::Ingestor`] should attach to every
+/// request. A trait rather than a fixed header map so a token can be
+/// refreshed between windows instead of being captured once at startup.
+#[async_trait]
+pub trait CredentialsProvider: Send + Sync {
+    async fn headers(&self) -> Result<Vec<(String, String)>>;
+}
+
+/// A [`CredentialsProvider`] that never changes after construction. Covers
+/// every [`Credentials`] variant; implement `CredentialsProvider` directly
+/// for anything that needs to refresh (e.g. re-fetching a short-lived token).
+pub struct StaticCredentials(Credentials);
+
+impl StaticCredentials {
+    pub fn new(credentials: Credentials) -> Self {
+        StaticCredentials(credentials)
+    }
+}
+
+#[async_trait]
+impl CredentialsProvider for StaticCredentials {
+    async fn headers(&self) -> Result<Vec<(String, String)>> {
+        Ok(match &self.0 {
+            Credentials::Bearer(token) => vec![("Authorization".to_string(), format!("Bearer {}", token))],
+            Credentials::Basic { username, password } => vec![(
+                "Authorization".to_string(),
+                format!("Basic {}", STANDARD.encode(format!("{}:{}", username, password))),
+            )],
+            Credentials::Headers(headers) => {
+                headers.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn bearer_sets_authorization_header() {
+        let creds = StaticCredentials::new(Credentials::Bearer("abc123".to_string()));
+        assert_eq!(
+            creds.headers().await.unwrap(),
+            vec![("Authorization".to_string(), "Bearer abc123".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn basic_base64_encodes_user_and_password() {
+        let creds = StaticCredentials::new(Credentials::Basic {
+            username: "user".to_string(),
+            password: "pass".to_string(),
+        });
+        assert_eq!(
+            creds.headers().await.unwrap(),
+            vec![("Authorization".to_string(), "Basic dXNlcjpwYXNz".to_string())]
+        );
+    }
+}
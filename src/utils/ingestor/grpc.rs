@@ -0,0 +1,223 @@
+// Copyright 2021 Developers of Pyroscope.
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0>. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{CredentialsProvider, IngestMetadata, Ingestor, SendError};
+use crate::error::{PyroscopeError, Result};
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tonic::metadata::{MetadataKey, MetadataValue};
+use tonic::transport::Channel;
+
+/// Pushes profiles to the Pyroscope push service / OTLP profiles endpoint
+/// over gRPC, reusing a single channel across uploads instead of
+/// reconnecting for every push.
+pub struct GrpcIngestor {
+    client: PusherClient<Channel>,
+    credentials: Option<Arc<dyn CredentialsProvider>>,
+}
+
+impl GrpcIngestor {
+    /// Connect to `endpoint` (e.g. `https://push.example.com:4040`),
+    /// enabling TLS when the endpoint scheme calls for it.
+    pub async fn connect<S: AsRef<str>>(endpoint: S) -> Result<Self> {
+        let mut builder = Channel::from_shared(endpoint.as_ref().to_string())
+            .map_err(|e| PyroscopeError::new(&format!("Invalid gRPC endpoint: {}", e)))?;
+
+        if endpoint.as_ref().starts_with("https://") {
+            builder = builder
+                .tls_config(tonic::transport::ClientTlsConfig::new())
+                .map_err(|e| PyroscopeError::new(&format!("Invalid TLS config: {}", e)))?;
+        }
+
+        let channel = builder
+            .connect()
+            .await
+            .map_err(|e| PyroscopeError::new(&format!("Failed to connect to {}: {}", endpoint.as_ref(), e)))?;
+
+        Ok(GrpcIngestor {
+            client: PusherClient::new(channel),
+            credentials: None,
+        })
+    }
+
+    /// Attach a [`CredentialsProvider`] whose headers are sent as gRPC
+    /// metadata on every push, queried fresh each time so a refreshable
+    /// token stays valid.
+    pub fn credentials(mut self, credentials: impl CredentialsProvider + 'static) -> Self {
+        self.credentials = Some(Arc::new(credentials));
+        self
+    }
+}
+
+#[async_trait]
+impl Ingestor for GrpcIngestor {
+    async fn push(&self, payload: Vec<u8>, metadata: &IngestMetadata) -> std::result::Result<(), SendError> {
+        if payload.is_empty() {
+            return Ok(());
+        }
+
+        let mut request = tonic::Request::new(PushRequest {
+            series: vec![RawProfileSeries {
+                labels: build_labels(&metadata.application_name, &metadata.tags),
+                samples: vec![RawSample { raw_profile: payload, id: 0 }],
+            }],
+        });
+
+        if let Some(credentials) = &self.credentials {
+            for (key, value) in credentials.headers().await.map_err(SendError::Permanent)? {
+                let key = MetadataKey::from_bytes(key.to_lowercase().as_bytes())
+                    .map_err(|e| SendError::Permanent(PyroscopeError::new(&format!("Invalid metadata key: {}", e))))?;
+                let value = MetadataValue::try_from(value.as_str())
+                    .map_err(|e| SendError::Permanent(PyroscopeError::new(&format!("Invalid metadata value: {}", e))))?;
+                request.metadata_mut().insert(key, value);
+            }
+        }
+
+        // `PusherClient` borrows mutably for readiness checks, so we clone
+        // the cheap `Channel` handle rather than requiring `&mut self`.
+        self.client.clone().push(request).await.map_err(|status| {
+            let err = PyroscopeError::new(&format!("gRPC push failed: {}", status));
+            if is_retryable(status.code()) {
+                SendError::Retryable(err)
+            } else {
+                SendError::Permanent(err)
+            }
+        })?;
+
+        Ok(())
+    }
+}
+
+/// gRPC statuses that are transient and worth retrying, mirroring the
+/// HTTP 5xx/timeout/connection split used by the HTTP transports.
+fn is_retryable(code: tonic::Code) -> bool {
+    matches!(
+        code,
+        tonic::Code::Unavailable
+            | tonic::Code::DeadlineExceeded
+            | tonic::Code::ResourceExhausted
+            | tonic::Code::Aborted
+            | tonic::Code::Internal
+    )
+}
+
+/// Build the series labels for a push: `__name__` from the application
+/// name, plus one label per tag. Pulled out of `push` so it's testable
+/// without a connected channel.
+fn build_labels(application_name: &str, tags: &HashMap<String, String>) -> Vec<LabelPair> {
+    std::iter::once(LabelPair { name: "__name__".to_string(), value: application_name.to_string() })
+        .chain(tags.iter().map(|(name, value)| LabelPair { name: name.clone(), value: value.clone() }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_retryable_classifies_transient_codes() {
+        assert!(is_retryable(tonic::Code::Unavailable));
+        assert!(is_retryable(tonic::Code::DeadlineExceeded));
+        assert!(is_retryable(tonic::Code::ResourceExhausted));
+        assert!(is_retryable(tonic::Code::Aborted));
+        assert!(is_retryable(tonic::Code::Internal));
+
+        assert!(!is_retryable(tonic::Code::InvalidArgument));
+        assert!(!is_retryable(tonic::Code::PermissionDenied));
+        assert!(!is_retryable(tonic::Code::NotFound));
+    }
+
+    #[test]
+    fn build_labels_includes_app_name_and_tags() {
+        let mut tags = HashMap::new();
+        tags.insert("env".to_string(), "staging".to_string());
+
+        let labels = build_labels("my.app", &tags);
+
+        assert!(labels.contains(&LabelPair { name: "__name__".to_string(), value: "my.app".to_string() }));
+        assert!(labels.contains(&LabelPair { name: "env".to_string(), value: "staging".to_string() }));
+        assert_eq!(labels.len(), 2);
+    }
+
+    #[test]
+    fn build_labels_with_no_tags_is_just_app_name() {
+        let labels = build_labels("my.app", &HashMap::new());
+        assert_eq!(labels, vec![LabelPair { name: "__name__".to_string(), value: "my.app".to_string() }]);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, prost::Message)]
+struct LabelPair {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct RawProfileSeries {
+    #[prost(message, repeated, tag = "1")]
+    labels: Vec<LabelPair>,
+    #[prost(message, repeated, tag = "2")]
+    samples: Vec<RawSample>,
+}
+
+/// A single raw `pprof` payload. `id` mirrors the upstream message's
+/// optional sample identifier; this client always sends the whole profile
+/// as one sample, so it's left at the default.
+#[derive(Clone, PartialEq, prost::Message)]
+struct RawSample {
+    #[prost(bytes = "vec", tag = "1")]
+    raw_profile: Vec<u8>,
+    #[prost(int64, tag = "2")]
+    id: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct PushRequest {
+    #[prost(message, repeated, tag = "1")]
+    series: Vec<RawProfileSeries>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct PushResponse {}
+
+/// Hand-rolled equivalent of the client `tonic-build` would generate from
+/// the push service's `.proto` definition, kept here so the crate doesn't
+/// need a protobuf toolchain just for this one RPC. Field numbers and
+/// nesting (`RawProfileSeries.samples` wrapping `RawSample.raw_profile`
+/// rather than a flat byte field) are modeled on the upstream
+/// `pyroscope.push.v1` schema as closely as possible without vendoring the
+/// real `.proto`. `IngestMetadata`'s `sample_rate`/`from`/`until` are an
+/// HTTP-ingest-route concept (they become query params in `HttpIngestor`);
+/// the upstream `pushv1` schema has no equivalent flat fields on
+/// `RawProfileSeries`, so they're intentionally not sent here. Treat this
+/// as provisional until it's checked against the real generated client.
+#[derive(Clone)]
+struct PusherClient<T> {
+    inner: tonic::client::Grpc<T>,
+}
+
+impl PusherClient<Channel> {
+    fn new(channel: Channel) -> Self {
+        PusherClient { inner: tonic::client::Grpc::new(channel) }
+    }
+
+    async fn push(
+        &mut self, request: tonic::Request<PushRequest>,
+    ) -> std::result::Result<tonic::Response<PushResponse>, tonic::Status> {
+        self.inner
+            .ready()
+            .await
+            .map_err(|e| tonic::Status::unknown(format!("gRPC channel not ready: {}", e)))?;
+
+        let codec = tonic::codec::ProstCodec::default();
+        let path = http::uri::PathAndQuery::from_static("/pyroscope.push.v1.PusherService/Push");
+        self.inner.unary(request, path, codec).await
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright 2021 Developers of Pyroscope.
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0>. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::transport::{HttpRequest, HttpTransport, ReqwestTransport};
+use super::{CredentialsProvider, IngestMetadata, Ingestor, SendError};
+use crate::utils::ProfileFormat;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Pushes profiles to the Pyroscope server's HTTP `/ingest` route. The
+/// wire send is delegated to an [`HttpTransport`] so the default
+/// `reqwest`/tokio client can be swapped for an executor-agnostic one.
+pub struct HttpIngestor {
+    url: String,
+    transport: Box<dyn HttpTransport>,
+    credentials: Option<Arc<dyn CredentialsProvider>>,
+}
+
+impl HttpIngestor {
+    /// Build an `HttpIngestor` using the default `reqwest` transport.
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        HttpIngestor::with_transport(url, ReqwestTransport::default())
+    }
+
+    /// Build an `HttpIngestor` that sends through `transport` instead of
+    /// `reqwest`, e.g. [`super::transport::SmolTransport`] for non-tokio executors.
+    pub fn with_transport<S: Into<String>>(url: S, transport: impl HttpTransport + 'static) -> Self {
+        HttpIngestor {
+            url: url.into(),
+            transport: Box::new(transport),
+            credentials: None,
+        }
+    }
+
+    /// Attach a [`CredentialsProvider`] whose headers are added to every
+    /// push, queried fresh each time so a refreshable token stays valid.
+    pub fn credentials(mut self, credentials: impl CredentialsProvider + 'static) -> Self {
+        self.credentials = Some(Arc::new(credentials));
+        self
+    }
+}
+
+#[async_trait]
+impl Ingestor for HttpIngestor {
+    async fn push(&self, payload: Vec<u8>, metadata: &IngestMetadata) -> std::result::Result<(), SendError> {
+        if payload.is_empty() {
+            return Ok(());
+        }
+
+        let (content_type, format_param) = match metadata.format {
+            ProfileFormat::Folded => ("binary/octet-stream", "folded"),
+            ProfileFormat::Pprof => ("application/octet-stream", "pprof"),
+        };
+
+        let mut headers = vec![("Content-Type".to_string(), content_type.to_string())];
+        if let Some(credentials) = &self.credentials {
+            headers.extend(credentials.headers().await.map_err(SendError::Permanent)?);
+        }
+
+        let request = HttpRequest {
+            url: format!("{}/ingest", self.url),
+            headers,
+            query: vec![
+                ("name".to_string(), metadata.application_name.clone()),
+                ("from".to_string(), format!("{}", metadata.from)),
+                ("until".to_string(), format!("{}", metadata.until)),
+                ("format".to_string(), format_param.to_string()),
+                ("sampleRate".to_string(), format!("{}", metadata.sample_rate)),
+                ("spyName".to_string(), "pprof-rs".to_string()),
+            ],
+            body: payload,
+        };
+
+        self.transport.send(request).await
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2021 Developers of Pyroscope.
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0>. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod credentials;
+mod grpc;
+mod http;
+mod retry;
+pub mod transport;
+
+pub use credentials::{Credentials, CredentialsProvider, StaticCredentials};
+pub use grpc::GrpcIngestor;
+pub use http::HttpIngestor;
+pub use retry::{RetryConfig, RetryingIngestor};
+pub use transport::{HttpRequest, HttpTransport, ReqwestTransport, SmolTransport};
+
+use crate::error::PyroscopeError;
+use crate::utils::ProfileFormat;
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+/// Everything an [`Ingestor`] needs to describe a payload, independent of
+/// which transport ends up carrying it.
+#[derive(Debug, Clone)]
+pub struct IngestMetadata {
+    pub application_name: String,
+    pub tags: HashMap<String, String>,
+    pub sample_rate: libc::c_int,
+    pub from: u64,
+    pub until: u64,
+    pub format: ProfileFormat,
+}
+
+/// Outcome of a failed push, distinguishing failures worth retrying (5xx,
+/// timeouts, connection errors) from ones a retry can't fix (4xx).
+#[derive(Debug)]
+pub enum SendError {
+    Retryable(PyroscopeError),
+    Permanent(PyroscopeError),
+}
+
+impl From<SendError> for PyroscopeError {
+    fn from(err: SendError) -> Self {
+        match err {
+            SendError::Retryable(e) | SendError::Permanent(e) => e,
+        }
+    }
+}
+
+/// Pushes an already-serialized profile payload to a Pyroscope-compatible
+/// backend. Implementations own their own connection/client state so a
+/// single instance can be reused across uploads.
+#[async_trait]
+pub trait Ingestor: Send + Sync {
+    async fn push(&self, payload: Vec<u8>, metadata: &IngestMetadata) -> std::result::Result<(), SendError>;
+}
@@ -0,0 +1,244 @@
+// Copyright 2021 Developers of Pyroscope.
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0>. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{IngestMetadata, Ingestor, SendError};
+
+use async_trait::async_trait;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Retry/backoff knobs, using full-jitter exponential backoff:
+/// `delay = rand(0, min(max_delay, base * 2^attempt))`.
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    pub base: Duration,
+    pub max_delay: Duration,
+    pub max_retries: u32,
+    /// Upper bound on how many failed payloads are held for re-flushing.
+    pub max_spilled: usize,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            base: Duration::from_millis(500),
+            max_delay: Duration::from_secs(300),
+            max_retries: 5,
+            max_spilled: 32,
+        }
+    }
+}
+
+/// Wraps any [`Ingestor`] with full-jitter exponential backoff and a bounded
+/// local spill queue: a window that exhausts its retries is held instead of
+/// dropped, and re-flushed on the next successful push. Backoff sleeps via
+/// `futures-timer` rather than `tokio::time`, so this works the same whether
+/// `inner` is an `HttpIngestor` on `ReqwestTransport`/`SmolTransport` or a
+/// `GrpcIngestor` - none of which should have to pull in a tokio reactor
+/// just to retry.
+pub struct RetryingIngestor<I: Ingestor> {
+    inner: I,
+    config: RetryConfig,
+    spilled: Mutex<Vec<(Vec<u8>, IngestMetadata)>>,
+}
+
+impl<I: Ingestor> RetryingIngestor<I> {
+    pub fn new(inner: I) -> Self {
+        RetryingIngestor::with_config(inner, RetryConfig::default())
+    }
+
+    pub fn with_config(inner: I, config: RetryConfig) -> Self {
+        RetryingIngestor {
+            inner,
+            config,
+            spilled: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Send `payload`, retrying retryable failures with full-jitter backoff
+    /// until `max_retries` is exhausted. The returned error keeps the
+    /// `Permanent`/`Retryable` distinction so callers only spill failures a
+    /// retry could plausibly fix.
+    async fn send_with_retry(&self, payload: &[u8], metadata: &IngestMetadata) -> std::result::Result<(), SendError> {
+        let mut attempt = 0u32;
+        loop {
+            match self.inner.push(payload.to_vec(), metadata).await {
+                Ok(()) => return Ok(()),
+                Err(SendError::Permanent(e)) => {
+                    log::error!("Pyroscope: ingest rejected the profile, not retrying: {}", e);
+                    return Err(SendError::Permanent(e));
+                }
+                Err(SendError::Retryable(e)) => {
+                    if attempt >= self.config.max_retries {
+                        log::warn!(
+                            "Pyroscope: ingest failed after {} retries: {}",
+                            self.config.max_retries, e
+                        );
+                        return Err(SendError::Retryable(e));
+                    }
+
+                    let delay = full_jitter_backoff(&self.config, attempt);
+                    log::warn!(
+                        "Pyroscope: ingest attempt {} failed ({}), retrying in {:?}",
+                        attempt + 1,
+                        e,
+                        delay
+                    );
+                    futures_timer::Delay::new(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Best-effort re-flush of whatever's spilled from previous windows.
+    /// Payloads that exhaust their retries again are put back, oldest first;
+    /// ones that come back `Permanent` (e.g. the server started rejecting
+    /// them outright) are dropped instead of occupying a slot forever.
+    async fn flush_spilled(&self) {
+        let backlog = std::mem::take(&mut *self.spilled.lock().unwrap());
+        for (payload, metadata) in backlog {
+            if let Err(SendError::Retryable(_)) = self.send_with_retry(&payload, &metadata).await {
+                self.spill(payload, metadata);
+            }
+        }
+    }
+
+    fn spill(&self, payload: Vec<u8>, metadata: IngestMetadata) {
+        let mut spilled = self.spilled.lock().unwrap();
+        if spilled.len() < self.config.max_spilled {
+            spilled.push((payload, metadata));
+        } else {
+            log::error!("Pyroscope: local ingest spill queue is full, dropping profile");
+        }
+    }
+}
+
+fn full_jitter_backoff(config: &RetryConfig, attempt: u32) -> Duration {
+    let exponential = config.base.saturating_mul(1u32 << attempt.min(31));
+    let capped = exponential.min(config.max_delay);
+    Duration::from_secs_f64(capped.as_secs_f64() * rand::random::<f64>())
+}
+
+#[async_trait]
+impl<I: Ingestor> Ingestor for RetryingIngestor<I> {
+    async fn push(&self, payload: Vec<u8>, metadata: &IngestMetadata) -> std::result::Result<(), SendError> {
+        self.flush_spilled().await;
+
+        match self.send_with_retry(&payload, metadata).await {
+            Ok(()) => Ok(()),
+            Err(SendError::Retryable(e)) => {
+                // Only a retry-exhausted failure is worth holding onto; a
+                // permanent one will just fail again on the next window.
+                self.spill(payload, metadata.clone());
+                Err(SendError::Retryable(e))
+            }
+            Err(SendError::Permanent(e)) => Err(SendError::Permanent(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PyroscopeError;
+    use crate::utils::ProfileFormat;
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicUsize;
+
+    /// An [`Ingestor`] whose outcomes are scripted up front, so the
+    /// spill/flush cycle can be tested without real network IO.
+    struct ScriptedIngestor {
+        responses: Mutex<Vec<std::result::Result<(), SendError>>>,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Ingestor for ScriptedIngestor {
+        async fn push(&self, _payload: Vec<u8>, _metadata: &IngestMetadata) -> std::result::Result<(), SendError> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let mut responses = self.responses.lock().unwrap();
+            if responses.is_empty() {
+                Ok(())
+            } else {
+                responses.remove(0)
+            }
+        }
+    }
+
+    fn metadata() -> IngestMetadata {
+        IngestMetadata {
+            application_name: "app".to_string(),
+            tags: HashMap::new(),
+            sample_rate: 100,
+            from: 0,
+            until: 10,
+            format: ProfileFormat::Folded,
+        }
+    }
+
+    #[test]
+    fn full_jitter_backoff_never_exceeds_the_cap() {
+        let config = RetryConfig {
+            base: Duration::from_millis(500),
+            max_delay: Duration::from_secs(1),
+            max_retries: 5,
+            max_spilled: 8,
+        };
+        for attempt in 0..10 {
+            assert!(full_jitter_backoff(&config, attempt) <= config.max_delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn permanent_failure_is_returned_without_spilling() {
+        let ingestor = RetryingIngestor::with_config(
+            ScriptedIngestor {
+                responses: Mutex::new(vec![Err(SendError::Permanent(PyroscopeError::new("bad auth")))]),
+                calls: AtomicUsize::new(0),
+            },
+            RetryConfig { max_retries: 2, ..RetryConfig::default() },
+        );
+
+        assert!(matches!(
+            ingestor.push(vec![1], &metadata()).await,
+            Err(SendError::Permanent(_))
+        ));
+        assert_eq!(ingestor.spilled.lock().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn retry_exhausted_failure_is_spilled_then_reflushed() {
+        let ingestor = RetryingIngestor::with_config(
+            ScriptedIngestor {
+                // First push exhausts all retries (3 attempts) and spills.
+                responses: Mutex::new(vec![
+                    Err(SendError::Retryable(PyroscopeError::new("timeout"))),
+                    Err(SendError::Retryable(PyroscopeError::new("timeout"))),
+                    Err(SendError::Retryable(PyroscopeError::new("timeout"))),
+                ]),
+                calls: AtomicUsize::new(0),
+            },
+            RetryConfig {
+                base: Duration::from_millis(1),
+                max_delay: Duration::from_millis(5),
+                max_retries: 2,
+                max_spilled: 8,
+            },
+        );
+
+        assert!(matches!(
+            ingestor.push(vec![1], &metadata()).await,
+            Err(SendError::Retryable(_))
+        ));
+        assert_eq!(ingestor.spilled.lock().unwrap().len(), 1);
+
+        // The next window's push flushes and succeeds (no more scripted
+        // failures left), so the spill queue drains back to empty.
+        assert!(ingestor.push(vec![2], &metadata()).await.is_ok());
+        assert_eq!(ingestor.spilled.lock().unwrap().len(), 0);
+    }
+}
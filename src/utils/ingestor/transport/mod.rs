@@ -0,0 +1,34 @@
+// Copyright 2021 Developers of Pyroscope.
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0>. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod reqwest_transport;
+mod smol_transport;
+
+pub use reqwest_transport::ReqwestTransport;
+pub use smol_transport::SmolTransport;
+
+use super::SendError;
+
+use async_trait::async_trait;
+
+/// A single outbound HTTP POST, kept transport-agnostic so [`super::HttpIngestor`]
+/// can run under any async executor, not just tokio.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub query: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Sends a single HTTP POST request. Implementations are free to pick
+/// whatever async executor and IO primitives they like; `HttpIngestor` only
+/// needs the returned future to resolve, tagged with whether a failure is
+/// worth retrying.
+#[async_trait]
+pub trait HttpTransport: Send + Sync {
+    async fn send(&self, request: HttpRequest) -> std::result::Result<(), SendError>;
+}
@@ -0,0 +1,65 @@
+// Copyright 2021 Developers of Pyroscope.
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0>. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::super::SendError;
+use super::{HttpRequest, HttpTransport};
+use crate::error::PyroscopeError;
+
+use async_trait::async_trait;
+
+/// The default transport: `reqwest` driven by whatever tokio reactor the
+/// host process already runs.
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        ReqwestTransport {
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl HttpTransport for ReqwestTransport {
+    async fn send(&self, request: HttpRequest) -> std::result::Result<(), SendError> {
+        let mut builder = self.client.post(&request.url);
+        for (key, value) in &request.headers {
+            builder = builder.header(key, value);
+        }
+
+        let response = builder
+            .query(&request.query)
+            .body(request.body)
+            .send()
+            .await
+            .map_err(|e| {
+                let err = PyroscopeError::new(&format!("Ingest request failed: {}", e));
+                if e.is_timeout() || e.is_connect() {
+                    SendError::Retryable(err)
+                } else {
+                    SendError::Permanent(err)
+                }
+            })?;
+
+        let status = response.status();
+        if status.is_server_error() {
+            return Err(SendError::Retryable(PyroscopeError::new(&format!(
+                "Ingest server returned {}",
+                status
+            ))));
+        }
+        if status.is_client_error() {
+            return Err(SendError::Permanent(PyroscopeError::new(&format!(
+                "Ingest server returned {}",
+                status
+            ))));
+        }
+
+        Ok(())
+    }
+}
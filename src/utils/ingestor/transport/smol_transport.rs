@@ -0,0 +1,166 @@
+// Copyright 2021 Developers of Pyroscope.
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0>. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::super::SendError;
+use super::{HttpRequest, HttpTransport};
+use crate::error::{PyroscopeError, Result};
+
+use async_io::Async;
+use async_trait::async_trait;
+use futures_lite::{AsyncReadExt, AsyncWriteExt};
+use std::net::TcpStream;
+
+/// A lightweight HTTP/1.1 client built directly on `async-io`'s
+/// `Async<TcpStream>`, for embedders running under `smol`/`async-std` or any
+/// other executor that can't drive a `reqwest`/tokio future.
+#[derive(Default)]
+pub struct SmolTransport;
+
+impl SmolTransport {
+    pub fn new() -> Self {
+        SmolTransport
+    }
+}
+
+#[async_trait]
+impl HttpTransport for SmolTransport {
+    async fn send(&self, request: HttpRequest) -> std::result::Result<(), SendError> {
+        let (host, port, path) = parse_url(&request.url).map_err(SendError::Permanent)?;
+
+        let mut stream = Async::<TcpStream>::connect((host.as_str(), port))
+            .await
+            .map_err(|e| {
+                SendError::Retryable(PyroscopeError::new(&format!("Failed to connect to {}:{}: {}", host, port, e)))
+            })?;
+
+        let mut query = String::new();
+        for (index, (key, value)) in request.query.iter().enumerate() {
+            query.push(if index == 0 { '?' } else { '&' });
+            query.push_str(&urlencode(key));
+            query.push('=');
+            query.push_str(&urlencode(value));
+        }
+
+        let mut head = format!(
+            "POST {}{} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+            path,
+            query,
+            host,
+            request.body.len()
+        );
+        for (key, value) in &request.headers {
+            head.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        head.push_str("\r\n");
+
+        stream
+            .write_all(head.as_bytes())
+            .await
+            .map_err(|e| SendError::Retryable(PyroscopeError::new(&format!("Failed to write request: {}", e))))?;
+        stream
+            .write_all(&request.body)
+            .await
+            .map_err(|e| SendError::Retryable(PyroscopeError::new(&format!("Failed to write body: {}", e))))?;
+
+        let mut response = Vec::new();
+        stream
+            .read_to_end(&mut response)
+            .await
+            .map_err(|e| SendError::Retryable(PyroscopeError::new(&format!("Failed to read response: {}", e))))?;
+
+        match parse_status(&response) {
+            Some(status) if (500..600).contains(&status) => Err(SendError::Retryable(PyroscopeError::new(
+                &format!("Ingest server returned HTTP {}", status),
+            ))),
+            Some(status) if (400..500).contains(&status) => Err(SendError::Permanent(PyroscopeError::new(
+                &format!("Ingest server returned HTTP {}", status),
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Pull the status code out of a raw `HTTP/1.1 <code> <reason>` response
+/// line. Returns `None` if the response doesn't look like a valid status
+/// line, which is treated as success since there's nothing actionable to
+/// retry on.
+fn parse_status(response: &[u8]) -> Option<u16> {
+    let line_end = response.iter().position(|&b| b == b'\n')?;
+    let line = std::str::from_utf8(&response[..line_end]).ok()?;
+    line.split_whitespace().nth(1)?.parse::<u16>().ok()
+}
+
+fn parse_url(url: &str) -> Result<(String, u16, String)> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| PyroscopeError::new("SmolTransport only supports plain http:// endpoints"))?;
+
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let path = format!("/{}", path);
+
+    let (host, port) = match authority.split_once(':') {
+        Some((host, port)) => (
+            host.to_string(),
+            port.parse::<u16>()
+                .map_err(|e| PyroscopeError::new(&format!("Invalid port in {}: {}", url, e)))?,
+        ),
+        None => (authority.to_string(), 80),
+    };
+
+    Ok((host, port, path))
+}
+
+fn urlencode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_url_splits_host_port_and_path() {
+        assert_eq!(
+            parse_url("http://example.com:4040/ingest").unwrap(),
+            ("example.com".to_string(), 4040, "/ingest".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_url_defaults_to_port_80_and_root_path() {
+        assert_eq!(parse_url("http://example.com").unwrap(), ("example.com".to_string(), 80, "/".to_string()));
+    }
+
+    #[test]
+    fn parse_url_rejects_non_http_schemes() {
+        assert!(parse_url("https://example.com").is_err());
+    }
+
+    #[test]
+    fn parse_status_reads_the_status_line() {
+        assert_eq!(parse_status(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"), Some(200));
+        assert_eq!(parse_status(b"HTTP/1.1 503 Service Unavailable\r\n\r\n"), Some(503));
+    }
+
+    #[test]
+    fn parse_status_on_malformed_response_is_none() {
+        assert_eq!(parse_status(b"not a status line"), None);
+        assert_eq!(parse_status(b""), None);
+    }
+
+    #[test]
+    fn urlencode_escapes_reserved_characters_and_passes_unreserved_ones_through() {
+        assert_eq!(urlencode("a b/c"), "a%20b%2Fc");
+        assert_eq!(urlencode("abc-_.~123"), "abc-_.~123");
+    }
+}
@@ -4,63 +4,73 @@
 // https://www.apache.org/licenses/LICENSE-2.0>. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::error::Result;
+mod ingestor;
+mod pprof_format;
+
+pub use ingestor::{
+    Credentials, CredentialsProvider, GrpcIngestor, HttpIngestor, HttpRequest, HttpTransport, IngestMetadata,
+    Ingestor, ReqwestTransport, RetryConfig, RetryingIngestor, SendError, SmolTransport, StaticCredentials,
+};
+pub use pprof_format::ProfileFormat;
+
+use crate::error::{PyroscopeError, Result};
 
 use pprof::Report;
 use std::collections::HashMap;
 
-pub async fn pyroscope_ingest<S: AsRef<str>, N: AsRef<str>>(
+/// Fold a `Report` according to `format` and push it through `ingestor`,
+/// carrying `application_name` and `tags` as [`IngestMetadata`] so every
+/// transport (including `GrpcIngestor`'s per-tag `Label`s) actually receives
+/// them.
+///
+/// `ingestor` is a caller-owned, long-lived handle rather than something
+/// built inside this function: a [`RetryingIngestor`]'s spill queue only
+/// does any good if it survives across windows, so the session that calls
+/// `pyroscope_ingest` once per report should construct its [`Ingestor`]
+/// (`HttpIngestor` with [`ReqwestTransport`] or [`SmolTransport`], or
+/// `GrpcIngestor`, optionally wrapped in a `RetryingIngestor` and carrying
+/// [`CredentialsProvider`] headers) once and hold onto it.
+pub async fn pyroscope_ingest<N: AsRef<str>>(
+    report: &Report,
     start: u64,
     sample_rate: libc::c_int,
-    buffer: Vec<u8>,
-    url: S,
+    ingestor: &dyn Ingestor,
     application_name: N,
+    tags: HashMap<String, String>,
+    format: ProfileFormat,
+    with_thread_name: bool,
 ) -> Result<()> {
-    //let mut buffer = Vec::new();
-
-            //report.fold(true, &mut buffer)?;
+    let buffer = match format {
+        ProfileFormat::Folded => {
+            let mut buffer = Vec::new();
+            fold(report, with_thread_name, &mut buffer)?;
+            buffer
+        }
+        ProfileFormat::Pprof => {
+            pprof_format::to_pprof_bytes(report, with_thread_name, sample_rate)?
+        }
+    };
 
-            if buffer.is_empty() {
-                return Ok(());
-            }
+    if buffer.is_empty() {
+        return Ok(());
+    }
 
-            let client = reqwest::Client::new();
-            // TODO: handle the error of this request
-
-            //let start: u64 = report
-                //.timing
-                //.start_time
-                //.duration_since(std::time::UNIX_EPOCH)
-                //?
-                //.as_secs();
-
-            //let new_start = std::time::SystemTime::now()
-                //.duration_since(std::time::UNIX_EPOCH)
-                //?
-                //.as_secs() - 10u64;
-
-            let s_start = start - start.checked_rem(10).unwrap();
-            // This assumes that the interval between start and until doesn't
-            // exceed 10s
-            let s_until = s_start + 10;
-
-            client
-                .post(format!("{}/ingest", url.as_ref()))
-                .header("Content-Type", "binary/octet-stream")
-                .query(&[
-                    ("name", application_name.as_ref()),
-                    ("from", &format!("{}", s_start)),
-                    ("until", &format!("{}", s_until)),
-                    ("format", "folded"),
-                    ("sampleRate", &format!("{}", sample_rate)),
-                    ("spyName", "pprof-rs"),
-                ])
-                .body(buffer)
-                .send()
-                .await?;
-
-            Ok(())
-        }
+    let s_start = start - start.checked_rem(10).unwrap();
+    // This assumes that the interval between start and until doesn't
+    // exceed 10s
+    let s_until = s_start + 10;
+
+    let metadata = IngestMetadata {
+        application_name: application_name.as_ref().to_string(),
+        tags,
+        sample_rate,
+        from: s_start,
+        until: s_until,
+        format,
+    };
+
+    ingestor.push(buffer, &metadata).await.map_err(PyroscopeError::from)
+}
 
 pub fn merge_tags_with_app_name(application_name: String, tags: HashMap<String, String>) -> Result<String> {
     let mut tags_vec = tags
@@ -0,0 +1,301 @@
+// Copyright 2021 Developers of Pyroscope.
+
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0>. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::error::{PyroscopeError, Result};
+
+use flate2::{write::GzEncoder, Compression};
+use pprof::protos::{Function, Label, Line, Location, Profile, Sample, ValueType};
+use pprof::Report;
+use prost::Message;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Wire format used when pushing a profile to the Pyroscope ingest API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    /// Collapsed-stack text: one `frame;frame;...;frame count` line per sample.
+    Folded,
+    /// gzip-compressed `pprof` protobuf, carrying line numbers, labels and
+    /// multiple sample value types.
+    Pprof,
+}
+
+/// Interns strings into a `pprof` string table, where index 0 is reserved
+/// for the empty string.
+#[derive(Default)]
+struct StringTable {
+    strings: Vec<String>,
+    indices: HashMap<String, i64>,
+}
+
+impl StringTable {
+    fn new() -> Self {
+        StringTable {
+            strings: vec![String::new()],
+            indices: HashMap::new(),
+        }
+    }
+
+    fn intern(&mut self, s: &str) -> i64 {
+        if s.is_empty() {
+            return 0;
+        }
+        if let Some(&index) = self.indices.get(s) {
+            return index;
+        }
+        let index = self.strings.len() as i64;
+        self.strings.push(s.to_string());
+        self.indices.insert(s.to_string(), index);
+        index
+    }
+}
+
+/// Serialize a `pprof-rs` [`Report`] into a gzip-compressed `profile.proto`
+/// payload, preserving the leaf-to-root frame ordering used by [`super::fold`],
+/// emitting one `Function`/`Line` per inlined symbol so line numbers and
+/// filenames survive, and tagging each sample with a `thread` label when
+/// `with_thread_name` is set.
+pub fn to_pprof_bytes(
+    report: &Report, with_thread_name: bool, sample_rate: libc::c_int,
+) -> Result<Vec<u8>> {
+    let mut strings = StringTable::new();
+    let mut functions: HashMap<(String, String), u64> = HashMap::new();
+    let mut function_table = Vec::new();
+    let mut locations: HashMap<Vec<(u64, i64)>, u64> = HashMap::new();
+    let mut location_table = Vec::new();
+    let mut samples = Vec::new();
+
+    let thread_key = strings.intern("thread");
+
+    for (key, value) in report.data.iter() {
+        let mut location_ids = Vec::with_capacity(key.frames.len());
+
+        // Leaf-to-root, matching the order `fold` writes collapsed stacks in.
+        for frame in key.frames.iter().rev() {
+            // A single native frame can carry several inlined symbols; each
+            // becomes its own `Line` against its own `Function` so inlining
+            // isn't lost, and the `Location` groups them the way a real
+            // address with inlined callees would.
+            let lines: Vec<Line> = frame
+                .iter()
+                .rev()
+                .map(|symbol| {
+                    let name = symbol.name();
+                    let filename = symbol.filename().unwrap_or_default();
+                    let lineno = symbol.lineno();
+
+                    let function_id = *functions
+                        .entry((name.clone(), filename.clone()))
+                        .or_insert_with(|| {
+                            let id = function_table.len() as u64 + 1;
+                            let name_idx = strings.intern(&name);
+                            let filename_idx = strings.intern(&filename);
+                            function_table.push(Function {
+                                id,
+                                name: name_idx,
+                                system_name: name_idx,
+                                filename: filename_idx,
+                                start_line: lineno as i64,
+                            });
+                            id
+                        });
+
+                    Line { function_id, line: lineno as i64 }
+                })
+                .collect();
+
+            let location_key: Vec<(u64, i64)> = lines.iter().map(|line| (line.function_id, line.line)).collect();
+            let location_id = *locations.entry(location_key).or_insert_with(|| {
+                let id = location_table.len() as u64 + 1;
+                location_table.push(Location {
+                    id,
+                    mapping_id: 0,
+                    address: 0,
+                    line: lines,
+                    is_folded: false,
+                });
+                id
+            });
+
+            location_ids.push(location_id);
+        }
+
+        let label = if with_thread_name {
+            let thread = if !key.thread_name.is_empty() {
+                key.thread_name.clone()
+            } else {
+                format!("{:?}", key.thread_id)
+            };
+            vec![Label {
+                key: thread_key,
+                str: strings.intern(&thread),
+                num: 0,
+                num_unit: 0,
+            }]
+        } else {
+            Vec::new()
+        };
+
+        samples.push(Sample {
+            location_id: location_ids,
+            value: vec![*value as i64],
+            label,
+        });
+    }
+
+    let samples_idx = strings.intern("samples");
+    let count_idx = strings.intern("count");
+    let cpu_idx = strings.intern("cpu");
+    let nanoseconds_idx = strings.intern("nanoseconds");
+
+    let profile = Profile {
+        sample_type: vec![ValueType {
+            r#type: samples_idx,
+            unit: count_idx,
+        }],
+        sample: samples,
+        mapping: Vec::new(),
+        location: location_table,
+        function: function_table,
+        string_table: strings.strings,
+        drop_frames: 0,
+        keep_frames: 0,
+        time_nanos: 0,
+        duration_nanos: 0,
+        period_type: Some(ValueType {
+            r#type: cpu_idx,
+            unit: nanoseconds_idx,
+        }),
+        period: 1_000_000_000i64 / (sample_rate.max(1) as i64),
+        comment: Vec::new(),
+        default_sample_type: 0,
+    };
+
+    let mut encoded = Vec::new();
+    profile
+        .encode(&mut encoded)
+        .map_err(|e| PyroscopeError::new(&format!("Failed to encode pprof profile: {}", e)))?;
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(&encoded)?;
+    gzip.finish()
+        .map_err(|e| PyroscopeError::new(&format!("Failed to gzip pprof profile: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pprof::{Frames, Symbol};
+    use std::io::Read as _;
+    use std::path::PathBuf;
+
+    #[test]
+    fn to_pprof_bytes_on_empty_report_is_valid_gzip() {
+        let report = Report::default();
+        let bytes = to_pprof_bytes(&report, true, 100).unwrap();
+
+        // A gzip stream always starts with the two-byte magic number.
+        assert_eq!(&bytes[0..2], &[0x1f, 0x8b]);
+    }
+
+    fn symbol(name: &str, filename: &str, lineno: u32) -> Symbol {
+        Symbol {
+            name: Some(name.as_bytes().to_vec()),
+            addr: None,
+            lineno: Some(lineno),
+            filename: Some(PathBuf::from(filename)),
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Profile {
+        let mut raw = Vec::new();
+        flate2::read::GzDecoder::new(bytes).read_to_end(&mut raw).unwrap();
+        Profile::decode(&raw[..]).unwrap()
+    }
+
+    #[test]
+    fn to_pprof_bytes_builds_per_symbol_function_and_location_tables() {
+        let mut data = HashMap::new();
+        data.insert(
+            Frames {
+                // Root-to-leaf storage order, as `fold` and `to_pprof_bytes`
+                // both assume: index 0 is `caller`, the leaf carries two
+                // symbols inlined into the same native frame.
+                frames: vec![
+                    vec![symbol("caller", "caller.rs", 10)],
+                    vec![symbol("inlined_b", "b.rs", 22), symbol("inlined_a", "a.rs", 7)],
+                ],
+                thread_name: "main".to_string(),
+                thread_id: 1,
+            },
+            5,
+        );
+        let report = Report { data };
+
+        let profile = decode(&to_pprof_bytes(&report, true, 100).unwrap());
+        let string_at = |idx: i64| profile.string_table[idx as usize].clone();
+
+        assert_eq!(profile.sample.len(), 1);
+        let sample = &profile.sample[0];
+        assert_eq!(sample.value, vec![5]);
+
+        // One Location per native frame: the leaf's two inlined symbols
+        // collapse into a single Location with two Lines, not two separate
+        // Locations and not one Function with a joined name.
+        assert_eq!(sample.location_id.len(), 2);
+        assert_eq!(profile.function.len(), 3); // caller, inlined_a, inlined_b
+
+        let leaf_location =
+            profile.location.iter().find(|loc| loc.id == sample.location_id[0]).expect("leaf location present");
+        assert_eq!(leaf_location.line.len(), 2);
+
+        let leaf_names: Vec<String> = leaf_location
+            .line
+            .iter()
+            .map(|line| {
+                let function = profile.function.iter().find(|f| f.id == line.function_id).unwrap();
+                string_at(function.name)
+            })
+            .collect();
+        assert_eq!(leaf_names, vec!["inlined_a".to_string(), "inlined_b".to_string()]);
+
+        let inlined_a_function = profile
+            .function
+            .iter()
+            .find(|f| string_at(f.name) == "inlined_a")
+            .expect("inlined_a function present");
+        assert_eq!(string_at(inlined_a_function.filename), "a.rs");
+        assert_eq!(inlined_a_function.start_line, 7);
+
+        let caller_location =
+            profile.location.iter().find(|loc| loc.id == sample.location_id[1]).expect("caller location present");
+        assert_eq!(caller_location.line.len(), 1);
+        assert_eq!(caller_location.line[0].line, 10);
+
+        let thread_label = sample.label.iter().find(|label| string_at(label.key) == "thread").expect("thread label");
+        assert_eq!(string_at(thread_label.str), "main");
+    }
+
+    #[test]
+    fn to_pprof_bytes_dedupes_functions_seen_in_multiple_samples() {
+        let mut data = HashMap::new();
+        data.insert(
+            Frames { frames: vec![vec![symbol("shared", "shared.rs", 1)]], thread_name: "a".to_string(), thread_id: 1 },
+            1,
+        );
+        data.insert(
+            Frames { frames: vec![vec![symbol("shared", "shared.rs", 1)]], thread_name: "b".to_string(), thread_id: 2 },
+            2,
+        );
+        let report = Report { data };
+
+        let profile = decode(&to_pprof_bytes(&report, true, 100).unwrap());
+
+        assert_eq!(profile.sample.len(), 2);
+        assert_eq!(profile.function.len(), 1);
+        assert_eq!(profile.location.len(), 1);
+    }
+}